@@ -1,12 +1,76 @@
 use std::cell::UnsafeCell;
 use std::ops::{Deref, DerefMut};
-use std::sync::{Mutex, Condvar};
+use std::sync::{Mutex, Condvar, PoisonError};
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub use std::sync::{LockResult, TryLockError, TryLockResult};
+
+// The result of a `read_timeout`/`write_timeout` call.
+pub type TimedLockResult<Guard> = Result<Guard, TimedLockError<Guard>>;
+
+// An error returned by `read_timeout`/`write_timeout` to indicate that the
+// lock is either poisoned or the wait timed out before it could be acquired.
+#[derive(Debug)]
+pub enum TimedLockError<Guard> {
+	Poisoned(PoisonError<Guard>),
+	TimedOut,
+}
 
 struct State{
 	actv_reader: i32,
 	actv_writer: i32,
 	wtng_reader: i32,
 	wtng_writer: i32,
+	// Set when a writer panics while holding its guard. Once set, every
+	// subsequent `read`/`write` still succeeds in acquiring the lock, but
+	// reports the poisoning to the caller so they can decide whether the
+	// protected data is still trustworthy.
+	poisoned: bool,
+	// `true` while an `RwLockUpgradableReadGuard` is outstanding. At most one
+	// upgradable reader may be active at a time, though it coexists with
+	// plain readers (it is also counted in `actv_reader`).
+	actv_upgradable: bool,
+	// Only meaningful under `Preference::PhaseFair`. `phase` counts the
+	// number of reader/writer phase transitions the lock has gone through;
+	// `phase_kind` says which class is currently allowed to be admitted.
+	phase: u64,
+	phase_kind: PhaseKind,
+	// Cumulative contention counters, surfaced via `stats`.
+	total_reads: u64,
+	total_writes: u64,
+	reader_blocks: u64,
+	writer_blocks: u64,
+}
+
+// Only meaningful under `Preference::PhaseFair`. See `Preference::PhaseFair`.
+#[derive(PartialEq)]
+enum PhaseKind {
+	Reader,
+	Writer,
+}
+
+// Whether a reader arriving now may be admitted immediately under
+// `Preference::PhaseFair`, per the rule documented on that variant.
+//
+// Unlike the writer predicate below, this has no `phase_kind` escape hatch:
+// a reader that arrives while a writer is waiting always joins the queue,
+// even if the lock is still nominally in a reader phase. Without that, a
+// continuous stream of overlapping readers arriving after the writer could
+// each individually satisfy `phase_kind == Reader` and keep joining the
+// active set forever, so `actv_reader` would never reach zero and the
+// waiting writer would starve -- exactly the failure mode `PhaseFair` exists
+// to bound.
+fn phase_fair_reader_admitted(state: &State) -> bool {
+	state.actv_writer == 0 && state.wtng_writer == 0
+}
+
+// Whether a writer arriving now may be admitted immediately under
+// `Preference::PhaseFair`, per the rule documented on that variant.
+fn phase_fair_writer_admitted(state: &State) -> bool {
+	state.actv_reader == 0 && state.actv_writer == 0
+		&& (state.wtng_reader == 0 || state.phase_kind == PhaseKind::Writer)
 }
 // Provides a reader-writer lock to protect data of type `T`
 pub struct RwLock<T> {
@@ -16,6 +80,13 @@ pub struct RwLock<T> {
 	state: Mutex<State>,
 	reader: Condvar,
 	writer: UnsafeCell<Vec<Condvar>>,
+	// Dedicated to `RwLockUpgradableReadGuard::upgrade`. Kept separate from
+	// `writer`, whose position-based Fifo/Lifo selection assumes every
+	// waiter there is a brand-new writer; an upgrader already holds a read
+	// slot instead, so folding it into that queue can park it behind a
+	// plain writer that itself can never proceed until the upgrader lets go
+	// of its read slot, deadlocking both.
+	upgrade: Condvar,
 }
 
 #[derive(PartialEq)]
@@ -24,10 +95,36 @@ pub enum Preference {
     // * Readers must wait when a writer is active.
     // * Writers must wait when a reader is active or waiting, or a writer is active.
     Reader,
-    // Writers-preferred: 
+    // Writers-preferred:
     // * Readers must wait when a writer is active or waiting.
     // * Writer must wait when a reader or writer is active.
     Writer,
+    // Phase-fair: the lock alternates between a reader phase, which admits
+    // every reader that arrived before it started as one batch, and a
+    // writer phase, which admits a single writer.
+    // * A reader is admitted immediately if no writer is active or waiting.
+    //   Otherwise it waits for the next reader phase, even if one arrived
+    //   while a reader phase was already in progress.
+    // * A writer is admitted immediately if no reader is active, and either
+    //   no reader is waiting or the lock is currently in a writer phase.
+    //   Otherwise it waits for the next writer phase.
+    // This bounds starvation for both classes: a reader waits at most one
+    // writer phase plus the in-progress reader phase, and a writer waits at
+    // most one reader phase.
+    PhaseFair,
+}
+
+// A snapshot of an `RwLock`'s contention state, returned by `RwLock::stats`
+#[derive(Debug, Clone, Copy)]
+pub struct LockStats {
+    pub actv_reader: i32,
+    pub actv_writer: i32,
+    pub wtng_reader: i32,
+    pub wtng_writer: i32,
+    pub total_reads: u64,
+    pub total_writes: u64,
+    pub reader_blocks: u64,
+    pub writer_blocks: u64,
 }
 
 // In which order to schedule threads
@@ -47,22 +144,34 @@ impl<T> RwLock<T> {
 		RwLock{ 
 			data: UnsafeCell::new(data), 
 			pref: pref, order: order, 
-			state: Mutex::new(State{ 
+			state: Mutex::new(State{
 				actv_reader: 0, actv_writer: 0,
-				wtng_reader: 0, wtng_writer: 0
+				wtng_reader: 0, wtng_writer: 0,
+				poisoned: false,
+				actv_upgradable: false,
+				phase: 0, phase_kind: PhaseKind::Reader,
+				total_reads: 0, total_writes: 0,
+				reader_blocks: 0, writer_blocks: 0,
 			}),
 			reader: Condvar::new(),
-			writer: UnsafeCell::new(Vec::new()), 
+			writer: UnsafeCell::new(Vec::new()),
+			upgrade: Condvar::new(),
 		}
 	}
 
 	// Requests a read lock, waits when necessary, and wakes up as soon as the lock becomes available.
-	// 
-	// Always returns Ok(_).
-	// (We declare this return type to be `Result` to be compatible with `std::sync::RwLock`)
-	pub fn read(&self) -> Result<RwLockReadGuard<T>, ()> {
+	//
+	// Returns `Err` if the lock is poisoned, i.e. a writer panicked while
+	// holding the write lock. The returned `PoisonError` still carries the
+	// guard, so callers can recover with `.into_inner()`.
+	pub fn read(&self) -> LockResult<RwLockReadGuard<T>> {
 		let mut state = self.state.lock().unwrap();
 		state.wtng_reader += 1;
+		let had_to_wait = match self.pref {
+			Preference::Reader 	=> state.actv_writer > 0,
+			Preference::Writer 	=> (state.actv_writer + state.wtng_writer) > 0,
+			Preference::PhaseFair 	=> !phase_fair_reader_admitted(&state),
+		};
 		match self.pref {
 			Preference::Reader 	=> {
 				while state.actv_writer > 0 {
@@ -72,23 +181,46 @@ impl<T> RwLock<T> {
 			Preference::Writer 	=> {
 				while (state.actv_writer + state.wtng_writer) > 0{
 					state = self.reader.wait(state).unwrap();
-				}				
+				}
+			},
+			Preference::PhaseFair 	=> {
+				while !phase_fair_reader_admitted(&state) {
+					state = self.reader.wait(state).unwrap();
+				}
 			},
 		}
 		state.wtng_reader -= 1;
 		state.actv_reader += 1;
-		Ok(RwLockReadGuard{ lock: &self })	
+		state.total_reads += 1;
+		if had_to_wait {
+			state.reader_blocks += 1;
+		}
+		let poisoned = state.poisoned;
+		drop(state);
+		let guard = RwLockReadGuard{ lock: &self };
+		if poisoned {
+			Err(PoisonError::new(guard))
+		} else {
+			Ok(guard)
+		}
 	}
 
 	// Requests a write lock, and waits when necessary.
 	// When the lock becomes available,
 	// * if `order == Order::Fifo`, wakes up the first thread
 	// * if `order == Order::Lifo`, wakes up the last thread
-	// 
-	// Always returns Ok(_).
-	pub fn write(&self) -> Result<RwLockWriteGuard<T>, ()> {
+	//
+	// Returns `Err` if the lock is poisoned, i.e. a previous writer panicked
+	// while holding the write lock. The returned `PoisonError` still carries
+	// the guard, so callers can recover with `.into_inner()`.
+	pub fn write(&self) -> LockResult<RwLockWriteGuard<T>> {
 		let mut state = self.state.lock().unwrap();
 		state.wtng_writer += 1;
+		let had_to_wait = state.wtng_writer != 1 || match self.pref {
+			Preference::Reader 	=> (state.actv_writer + state.actv_reader + state.wtng_reader) > 0,
+			Preference::Writer 	=> (state.actv_writer + state.actv_reader) > 0,
+			Preference::PhaseFair 	=> !phase_fair_writer_admitted(&state),
+		};
 		let vec = unsafe{ &mut *self.writer.get() };
 		vec.push(Condvar::new());
 		{ 	let refe = &vec[vec.len()-1];
@@ -106,6 +238,11 @@ impl<T> RwLock<T> {
 						state = refe.wait(state).unwrap();
 					}
 				},
+				Preference::PhaseFair 	=> {
+					while !phase_fair_writer_admitted(&state) {
+						state = refe.wait(state).unwrap();
+					}
+				},
 		}}
 		match self.order{
 			Order::Fifo	=> { vec.remove(0); },
@@ -113,7 +250,281 @@ impl<T> RwLock<T> {
 		}
 		state.wtng_writer -= 1;
 		state.actv_writer += 1;
-		Ok(RwLockWriteGuard{ lock: &self })
+		state.total_writes += 1;
+		if had_to_wait {
+			state.writer_blocks += 1;
+		}
+		let poisoned = state.poisoned;
+		drop(state);
+		let guard = RwLockWriteGuard{ lock: &self };
+		if poisoned {
+			Err(PoisonError::new(guard))
+		} else {
+			Ok(guard)
+		}
+	}
+
+	// Returns `true` if the lock is poisoned, i.e. a writer has panicked while
+	// holding the write lock.
+	pub fn is_poisoned(&self) -> bool {
+		self.state.lock().unwrap().poisoned
+	}
+
+	// Clears the poisoned state of the lock, so future calls to `read`/`write`
+	// succeed as normal instead of reporting a `PoisonError`.
+	//
+	// This is appropriate when the caller has inspected the data protected by
+	// the lock and is confident it is still in a consistent state.
+	pub fn clear_poison(&self) {
+		self.state.lock().unwrap().poisoned = false;
+	}
+
+	// Attempts to acquire a read lock without waiting.
+	//
+	// Uses the same admission predicate as `read`, so it never has to enqueue
+	// on `reader`: if the predicate says this thread would have to wait,
+	// returns `Err(TryLockError::WouldBlock)` immediately instead.
+	pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<T>> {
+		let mut state = self.state.lock().unwrap();
+		let blocked = match self.pref {
+			Preference::Reader 	=> state.actv_writer > 0,
+			Preference::Writer 	=> (state.actv_writer + state.wtng_writer) > 0,
+			Preference::PhaseFair 	=> !phase_fair_reader_admitted(&state),
+		};
+		if blocked {
+			return Err(TryLockError::WouldBlock);
+		}
+		state.actv_reader += 1;
+		state.total_reads += 1;
+		let poisoned = state.poisoned;
+		drop(state);
+		let guard = RwLockReadGuard{ lock: &self };
+		if poisoned {
+			Err(TryLockError::Poisoned(PoisonError::new(guard)))
+		} else {
+			Ok(guard)
+		}
+	}
+
+	// Attempts to acquire a write lock without waiting.
+	//
+	// Uses the same admission predicate as `write`, so it never has to
+	// enqueue on `writer`: if the predicate says this thread would have to
+	// wait, returns `Err(TryLockError::WouldBlock)` immediately instead.
+	pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<T>> {
+		let mut state = self.state.lock().unwrap();
+		let blocked = match self.pref {
+			Preference::Reader 	=> (state.actv_writer + state.actv_reader + state.wtng_reader) > 0,
+			Preference::Writer 	=> (state.actv_writer + state.actv_reader) > 0,
+			Preference::PhaseFair 	=> !phase_fair_writer_admitted(&state),
+		};
+		if blocked {
+			return Err(TryLockError::WouldBlock);
+		}
+		state.actv_writer += 1;
+		state.total_writes += 1;
+		let poisoned = state.poisoned;
+		drop(state);
+		let guard = RwLockWriteGuard{ lock: &self };
+		if poisoned {
+			Err(TryLockError::Poisoned(PoisonError::new(guard)))
+		} else {
+			Ok(guard)
+		}
+	}
+
+	// Requests a read lock, waiting at most `dur` for it to become available.
+	//
+	// Mirrors the wait loop in `read`, but re-checks the remaining time on
+	// every spurious wakeup and gives up with `Err(TimedLockError::TimedOut)`
+	// once `dur` has elapsed, leaving `wtng_reader` as it found it.
+	pub fn read_timeout(&self, dur: Duration) -> TimedLockResult<RwLockReadGuard<T>> {
+		let deadline = Instant::now() + dur;
+		let mut state = self.state.lock().unwrap();
+		state.wtng_reader += 1;
+		let mut had_to_wait = false;
+		while match self.pref {
+			Preference::Reader 	=> state.actv_writer > 0,
+			Preference::Writer 	=> (state.actv_writer + state.wtng_writer) > 0,
+			Preference::PhaseFair 	=> !phase_fair_reader_admitted(&state),
+		} {
+			had_to_wait = true;
+			let remaining = match deadline.checked_duration_since(Instant::now()) {
+				Some(remaining) if !remaining.is_zero() => remaining,
+				_ => {
+					state.wtng_reader -= 1;
+					return Err(TimedLockError::TimedOut);
+				},
+			};
+			state = self.reader.wait_timeout(state, remaining).unwrap().0;
+		}
+		state.wtng_reader -= 1;
+		state.actv_reader += 1;
+		state.total_reads += 1;
+		if had_to_wait {
+			state.reader_blocks += 1;
+		}
+		let poisoned = state.poisoned;
+		drop(state);
+		let guard = RwLockReadGuard{ lock: &self };
+		if poisoned {
+			Err(TimedLockError::Poisoned(PoisonError::new(guard)))
+		} else {
+			Ok(guard)
+		}
+	}
+
+	// Requests a write lock, waiting at most `dur` for it to become
+	// available.
+	//
+	// Mirrors the wait loop in `write`, but re-checks the remaining time on
+	// every spurious wakeup. On expiry it unwinds the bookkeeping `write`
+	// would otherwise have left behind: `wtng_writer` is decremented and the
+	// `Condvar` this call pushed onto `writer` is removed, before returning
+	// `Err(TimedLockError::TimedOut)`.
+	pub fn write_timeout(&self, dur: Duration) -> TimedLockResult<RwLockWriteGuard<T>> {
+		let deadline = Instant::now() + dur;
+		let mut state = self.state.lock().unwrap();
+		state.wtng_writer += 1;
+		let vec = unsafe{ &mut *self.writer.get() };
+		vec.push(Condvar::new());
+		let cv_ptr: *const Condvar = &vec[vec.len()-1];
+		let mut had_to_wait = state.wtng_writer != 1;
+		{	let refe = unsafe{ &*cv_ptr };
+			if state.wtng_writer != 1 {
+				match deadline.checked_duration_since(Instant::now()) {
+					Some(remaining) if !remaining.is_zero() => {
+						state = refe.wait_timeout(state, remaining).unwrap().0;
+					},
+					_ => {
+						self.remove_waiting_writer(&mut state, cv_ptr);
+						return Err(TimedLockError::TimedOut);
+					},
+				}
+			}
+			while match self.pref {
+				Preference::Reader 	=> (state.actv_writer + state.actv_reader + state.wtng_reader) > 0,
+				Preference::Writer 	=> (state.actv_writer + state.actv_reader) > 0,
+				Preference::PhaseFair 	=> !phase_fair_writer_admitted(&state),
+			} {
+				had_to_wait = true;
+				let remaining = match deadline.checked_duration_since(Instant::now()) {
+					Some(remaining) if !remaining.is_zero() => remaining,
+					_ => {
+						self.remove_waiting_writer(&mut state, cv_ptr);
+						return Err(TimedLockError::TimedOut);
+					},
+				};
+				state = refe.wait_timeout(state, remaining).unwrap().0;
+			}
+		}
+		self.remove_waiting_writer(&mut state, cv_ptr);
+		state.actv_writer += 1;
+		state.total_writes += 1;
+		if had_to_wait {
+			state.writer_blocks += 1;
+		}
+		let poisoned = state.poisoned;
+		drop(state);
+		let guard = RwLockWriteGuard{ lock: &self };
+		if poisoned {
+			Err(TimedLockError::Poisoned(PoisonError::new(guard)))
+		} else {
+			Ok(guard)
+		}
+	}
+
+	// Requests an upgradable read lock, waits when necessary.
+	//
+	// An upgradable read coexists with plain readers, but at most one
+	// upgradable reader may be active at a time, so it blocks while another
+	// upgradable read is active. It is otherwise admitted under the same
+	// `self.pref` rule as a plain `read`, so it can't be used to barge ahead
+	// of an already-waiting writer under `Preference::Writer`, or ahead of a
+	// pending writer phase under `Preference::PhaseFair`. Holding the
+	// returned guard lets a caller check a condition and later call
+	// `upgrade()` to gain exclusive access without releasing the lock in
+	// between.
+	pub fn upgradable_read(&self) -> LockResult<RwLockUpgradableReadGuard<T>> {
+		let mut state = self.state.lock().unwrap();
+		state.wtng_reader += 1;
+		while state.actv_upgradable || match self.pref {
+			Preference::Reader 	=> state.actv_writer > 0,
+			Preference::Writer 	=> (state.actv_writer + state.wtng_writer) > 0,
+			Preference::PhaseFair 	=> !phase_fair_reader_admitted(&state),
+		} {
+			state = self.reader.wait(state).unwrap();
+		}
+		state.wtng_reader -= 1;
+		state.actv_reader += 1;
+		state.actv_upgradable = true;
+		let poisoned = state.poisoned;
+		drop(state);
+		let guard = RwLockUpgradableReadGuard{ lock: &self };
+		if poisoned {
+			Err(PoisonError::new(guard))
+		} else {
+			Ok(guard)
+		}
+	}
+
+	// Returns a mutable reference to the underlying data, without locking.
+	//
+	// Safe because `&mut self` is proof of exclusive access to the lock.
+	//
+	// Returns `Err` if the lock is poisoned, i.e. a writer panicked while
+	// holding the write lock.
+	pub fn get_mut(&mut self) -> LockResult<&mut T> {
+		let poisoned = self.state.lock().unwrap().poisoned;
+		let data = unsafe{ &mut *self.data.get() };
+		if poisoned {
+			Err(PoisonError::new(data))
+		} else {
+			Ok(data)
+		}
+	}
+
+	// Consumes the lock, returning the underlying data.
+	//
+	// Returns `Err` if the lock is poisoned, i.e. a writer panicked while
+	// holding the write lock.
+	pub fn into_inner(self) -> LockResult<T> {
+		let poisoned = self.state.lock().unwrap().poisoned;
+		let data = self.data.into_inner();
+		if poisoned {
+			Err(PoisonError::new(data))
+		} else {
+			Ok(data)
+		}
+	}
+
+	// Returns a snapshot of the lock's current contention state and
+	// cumulative scheduling counters, useful for measuring how well a given
+	// `Preference`/`Order` configuration performs under a given workload.
+	pub fn stats(&self) -> LockStats {
+		let state = self.state.lock().unwrap();
+		LockStats {
+			actv_reader: state.actv_reader,
+			actv_writer: state.actv_writer,
+			wtng_reader: state.wtng_reader,
+			wtng_writer: state.wtng_writer,
+			total_reads: state.total_reads,
+			total_writes: state.total_writes,
+			reader_blocks: state.reader_blocks,
+			writer_blocks: state.writer_blocks,
+		}
+	}
+
+	// Removes `cv_ptr` from the `writer` queue (identified by pointer, since
+	// positions shift as other writers are admitted) and decrements
+	// `wtng_writer`. Shared cleanup for both the success and timeout paths of
+	// `write_timeout`.
+	fn remove_waiting_writer(&self, state: &mut State, cv_ptr: *const Condvar) {
+		state.wtng_writer -= 1;
+		let vec = unsafe{ &mut *self.writer.get() };
+		if let Some(pos) = vec.iter().position(|cv| std::ptr::eq(cv, cv_ptr)) {
+			vec.remove(pos);
+		}
 	}
 
 	fn pick_writer(&self) {
@@ -141,15 +552,67 @@ pub struct RwLockReadGuard<'a, T: 'a> {
 pub struct RwLockWriteGuard<'a, T: 'a> {
 	lock: &'a RwLock<T>,
 }
+// An upgradable read guard for `RwLock`, obtained via `upgradable_read`
+pub struct RwLockUpgradableReadGuard<'a, T: 'a> {
+	lock: &'a RwLock<T>,
+}
+
+impl<'a, T> RwLockUpgradableReadGuard<'a, T> {
+	// Atomically transitions from an upgradable read lock to a write lock,
+	// without ever releasing the lock in between. This eliminates the
+	// read-drop-write race of checking a condition under a read lock, then
+	// reacquiring the lock to act on it.
+	//
+	// Waits on the dedicated `upgrade` `Condvar` until `actv_reader` drops to
+	// the single reader held by this upgrader, then flips it to an active
+	// writer. This deliberately does not enqueue on the `writer` vec used by
+	// `write`: that queue's Fifo/Lifo selection picks one specific waiter to
+	// notify at a time, on the assumption every waiter there is a brand-new
+	// writer whose own admission only needs readers and writers to drain. An
+	// upgrader already holds one of those reader slots itself, so if a plain
+	// `write()` call is queued ahead of it, that writer can never be
+	// admitted (it waits on `actv_reader == 0`, which can't happen until the
+	// upgrader proceeds) while the upgrader's own turn never comes either
+	// (the queue keeps re-notifying the writer in front of it) — both
+	// threads wait forever. Waiting on a Condvar of its own sidesteps that
+	// queue entirely.
+	pub fn upgrade(self) -> RwLockWriteGuard<'a, T> {
+		let lock = self.lock;
+		let mut state = lock.state.lock().unwrap();
+		while state.actv_reader > 1 {
+			state = lock.upgrade.wait(state).unwrap();
+		}
+		state.actv_reader -= 1;
+		state.actv_upgradable = false;
+		state.actv_writer += 1;
+		drop(state);
+		// The state above already accounts for releasing the upgradable read
+		// and acquiring the write lock, so the guard must not run its own
+		// `Drop` logic.
+		mem::forget(self);
+		RwLockWriteGuard{ lock: lock }
+	}
+}
 
 // Releases the read lock
 impl<'a, T> Drop for RwLockReadGuard<'a, T> {
 	fn drop(&mut self){
 		let mut state = self.lock.state.lock().unwrap();
 		state.actv_reader -= 1;
-		if state.wtng_writer > 0 {
+		if self.lock.pref == Preference::PhaseFair {
+			// Only the last reader of the current batch gets to hand the
+			// lock over to the writer phase.
+			if state.actv_reader == 0 && state.wtng_writer > 0 {
+				state.phase += 1;
+				state.phase_kind = PhaseKind::Writer;
+				self.lock.pick_writer();
+			}
+		} else if state.wtng_writer > 0 {
 			self.lock.pick_writer();
 		}
+		// Wakes an `upgrade()` call waiting for this to be the last other
+		// reader sharing the lock with it.
+		self.lock.upgrade.notify_all();
 	}
 }
 
@@ -157,6 +620,9 @@ impl<'a, T> Drop for RwLockReadGuard<'a, T> {
 impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
 	fn drop(&mut self){
 		let mut state = self.lock.state.lock().unwrap();
+		if thread::panicking() {
+			state.poisoned = true;
+		}
 		state.actv_writer -= 1;
 		match self.lock.pref {
 			Preference::Reader 	=>{
@@ -173,10 +639,52 @@ impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
 					self.lock.reader.notify_all();
 				}
 			},
+			Preference::PhaseFair 	=>{
+				if state.wtng_reader > 0 {
+					state.phase += 1;
+					state.phase_kind = PhaseKind::Reader;
+					self.lock.reader.notify_all();
+				} else if state.wtng_writer > 0 {
+					state.phase += 1;
+					state.phase_kind = PhaseKind::Writer;
+					self.lock.pick_writer();
+				}
+			},
 		}
 	}
 }
 
+// Releases the upgradable read lock
+impl<'a, T> Drop for RwLockUpgradableReadGuard<'a, T> {
+	fn drop(&mut self){
+		let mut state = self.lock.state.lock().unwrap();
+		state.actv_reader -= 1;
+		state.actv_upgradable = false;
+		if self.lock.pref == Preference::PhaseFair {
+			// Only the last reader of the current batch gets to hand the
+			// lock over to the writer phase.
+			if state.actv_reader == 0 && state.wtng_writer > 0 {
+				state.phase += 1;
+				state.phase_kind = PhaseKind::Writer;
+				self.lock.pick_writer();
+			}
+		} else if state.wtng_writer > 0 {
+			self.lock.pick_writer();
+		}
+		// Wakes any thread blocked in `upgradable_read`, since the flag it
+		// was waiting on just cleared.
+		self.lock.reader.notify_all();
+	}
+}
+
+// Provides access to the shared object
+impl<'a, T> Deref for RwLockUpgradableReadGuard<'a, T> {
+	type Target = T;
+	fn deref(&self) -> &T {
+		unsafe{ & *self.lock.data.get() }
+	}
+}
+
 // Provides access to the shared object
 impl<'a, T> Deref for RwLockReadGuard<'a, T> {
 	type Target = T;
@@ -197,3 +705,269 @@ impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
 		unsafe{ &mut *self.lock.data.get() }
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::Arc;
+	use std::sync::mpsc;
+	use std::time::Duration;
+
+	// Runs `f` on a background thread and fails the test if it doesn't
+	// finish within `timeout`, instead of hanging the test binary forever --
+	// the appropriate way to turn a deadlock into a regular test failure.
+	fn assert_completes_within<F: FnOnce() + Send + 'static>(timeout: Duration, f: F) {
+		let (tx, rx) = mpsc::channel();
+		thread::spawn(move || {
+			f();
+			let _ = tx.send(());
+		});
+		rx.recv_timeout(timeout)
+			.expect("operation did not complete in time (likely deadlocked)");
+	}
+
+	#[test]
+	fn upgrade_does_not_deadlock_with_a_queued_writer() {
+		assert_completes_within(Duration::from_secs(5), || {
+			let lock = Arc::new(RwLock::new(0, Preference::Reader, Order::Fifo));
+
+			// Hold a plain reader alongside the upgrader, so `upgrade()` has
+			// to wait for it to drop before `actv_reader` reaches 1.
+			let extra_reader = lock.read().unwrap();
+			let upgradable = lock.upgradable_read().unwrap();
+
+			// Queue a genuine writer behind the upgrader.
+			let lock2 = lock.clone();
+			let writer = thread::spawn(move || {
+				let mut w = lock2.write().unwrap();
+				*w += 1;
+			});
+
+			// Give the writer a chance to enqueue before releasing the reader.
+			thread::sleep(Duration::from_millis(50));
+			drop(extra_reader);
+
+			let mut w = upgradable.upgrade();
+			*w += 1;
+			drop(w);
+
+			writer.join().unwrap();
+		});
+	}
+
+	#[test]
+	fn upgradable_read_does_not_barge_ahead_of_a_queued_writer_under_writer_preference() {
+		assert_completes_within(Duration::from_secs(5), || {
+			let lock = Arc::new(RwLock::new(0, Preference::Writer, Order::Fifo));
+			let order_log = Arc::new(Mutex::new(Vec::new()));
+
+			// Hold a reader so the writer below has to queue instead of
+			// running immediately.
+			let reader = lock.read().unwrap();
+
+			let lock_w = lock.clone();
+			let log_w = order_log.clone();
+			let writer = thread::spawn(move || {
+				let mut w = lock_w.write().unwrap();
+				log_w.lock().unwrap().push("writer");
+				*w += 1;
+			});
+
+			// Give the writer time to register itself as waiting before the
+			// upgradable read arrives.
+			thread::sleep(Duration::from_millis(50));
+
+			let lock_u = lock.clone();
+			let log_u = order_log.clone();
+			let upgradable = thread::spawn(move || {
+				let g = lock_u.upgradable_read().unwrap();
+				log_u.lock().unwrap().push("upgradable");
+				drop(g);
+			});
+
+			drop(reader);
+			writer.join().unwrap();
+			upgradable.join().unwrap();
+
+			assert_eq!(*order_log.lock().unwrap(), vec!["writer", "upgradable"]);
+		});
+	}
+
+	#[test]
+	fn upgradable_read_participates_in_phase_fair_writer_handoff() {
+		assert_completes_within(Duration::from_secs(5), || {
+			let lock = Arc::new(RwLock::new(0, Preference::PhaseFair, Order::Fifo));
+			let order_log = Arc::new(Mutex::new(Vec::new()));
+
+			// Reader phase: a plain reader and an upgradable reader overlap.
+			let plain_reader = lock.read().unwrap();
+			let upgradable = lock.upgradable_read().unwrap();
+
+			// Queue a writer; phase-fair means it must wait for the whole
+			// reader batch (both of the above) to drain, not just the plain
+			// reader.
+			let lock_w = lock.clone();
+			let log_w = order_log.clone();
+			let writer = thread::spawn(move || {
+				let mut w = lock_w.write().unwrap();
+				log_w.lock().unwrap().push("writer");
+				*w += 1;
+			});
+			thread::sleep(Duration::from_millis(50));
+			assert_eq!(lock.stats().actv_writer, 0);
+
+			order_log.lock().unwrap().push("plain_reader_done");
+			drop(plain_reader);
+			thread::sleep(Duration::from_millis(50));
+			// Dropping only the plain reader must not let the writer in yet
+			// -- the upgradable reader is still part of the same batch.
+			assert_eq!(lock.stats().actv_writer, 0);
+
+			order_log.lock().unwrap().push("upgradable_done");
+			drop(upgradable);
+
+			writer.join().unwrap();
+			assert_eq!(
+				*order_log.lock().unwrap(),
+				vec!["plain_reader_done", "upgradable_done", "writer"]
+			);
+		});
+	}
+
+	#[test]
+	fn phase_fair_writer_is_not_starved_by_a_continuous_stream_of_readers() {
+		assert_completes_within(Duration::from_secs(5), || {
+			let lock = Arc::new(RwLock::new(0, Preference::PhaseFair, Order::Fifo));
+			let stop = Arc::new(Mutex::new(false));
+
+			// Keep a reader held continuously, swapping in a freshly
+			// `try_read`-ed one whenever possible so `actv_reader` never
+			// drops to zero on its own. Once the writer below is waiting,
+			// `try_read` must start failing -- a reader predicate that kept
+			// admitting readers during a reader phase despite the waiting
+			// writer would let it succeed forever (as long as some reader
+			// stayed active), and the writer would never see an opening.
+			let lock_r = lock.clone();
+			let stop_r = stop.clone();
+			let flood = thread::spawn(move || {
+				// `held`'s only job is to keep the guard alive via RAII;
+				// the `is_some()` checks below just keep the compiler from
+				// flagging its reassignment as a dead store.
+				let mut held = Some(lock_r.read().unwrap());
+				while !*stop_r.lock().unwrap() {
+					let _ = held.is_some();
+					held = match lock_r.try_read() {
+						Ok(next) => Some(next),
+						Err(_) => None,
+					};
+				}
+				let _ = held.is_some();
+			});
+
+			// Give the flood a moment to start before the writer queues.
+			thread::sleep(Duration::from_millis(20));
+			let lock_w = lock.clone();
+			let writer = thread::spawn(move || {
+				let mut w = lock_w.write().unwrap();
+				*w += 1;
+			});
+
+			writer.join().unwrap();
+			*stop.lock().unwrap() = true;
+			flood.join().unwrap();
+		});
+	}
+
+	#[test]
+	fn poisoning_round_trips_through_clear_poison() {
+		let lock = Arc::new(RwLock::new(0, Preference::Reader, Order::Fifo));
+
+		let lock2 = lock.clone();
+		let result = thread::spawn(move || {
+			let mut w = lock2.write().unwrap();
+			*w += 1;
+			panic!("poisoning the lock");
+		}).join();
+		assert!(result.is_err());
+
+		assert!(lock.is_poisoned());
+		match lock.read() {
+			Ok(_) => panic!("expected read() to report poisoning"),
+			Err(err) => assert_eq!(*err.into_inner(), 1),
+		}
+
+		lock.clear_poison();
+		assert!(!lock.is_poisoned());
+		assert_eq!(*lock.read().unwrap(), 1);
+	}
+
+	#[test]
+	fn try_read_and_try_write_respect_preference_without_blocking() {
+		// An active reader blocks try_write, and an active writer blocks
+		// try_read, under any preference.
+		let lock = RwLock::new(0, Preference::Reader, Order::Fifo);
+		let r = lock.read().unwrap();
+		assert!(lock.try_write().is_err());
+		drop(r);
+		assert!(lock.try_write().is_ok());
+
+		let w = lock.write().unwrap();
+		assert!(lock.try_read().is_err());
+		drop(w);
+		assert!(lock.try_read().is_ok());
+
+		// Under Preference::Writer, a merely *waiting* writer is enough to
+		// block a new try_read, even though no writer is active yet.
+		let lock = Arc::new(RwLock::new(0, Preference::Writer, Order::Fifo));
+		let r = lock.read().unwrap();
+		let lock2 = lock.clone();
+		let writer = thread::spawn(move || {
+			let mut w = lock2.write().unwrap();
+			*w += 1;
+		});
+		// Give the writer a chance to register itself as waiting.
+		thread::sleep(Duration::from_millis(50));
+		assert!(lock.try_read().is_err());
+		drop(r);
+		writer.join().unwrap();
+		assert!(lock.try_read().is_ok());
+	}
+
+	#[test]
+	fn read_timeout_and_write_timeout_clean_up_waiting_counts_on_expiry() {
+		let lock = RwLock::new(0, Preference::Reader, Order::Fifo);
+		let w = lock.write().unwrap();
+
+		let read_result = lock.read_timeout(Duration::from_millis(20));
+		assert!(matches!(read_result, Err(TimedLockError::TimedOut)));
+		assert_eq!(lock.stats().wtng_reader, 0);
+
+		let write_result = lock.write_timeout(Duration::from_millis(20));
+		assert!(matches!(write_result, Err(TimedLockError::TimedOut)));
+		assert_eq!(lock.stats().wtng_writer, 0);
+
+		drop(w);
+		assert!(lock.read_timeout(Duration::from_secs(1)).is_ok());
+	}
+
+	#[test]
+	fn get_mut_and_into_inner_report_poisoning() {
+		let lock = Arc::new(RwLock::new(0, Preference::Reader, Order::Fifo));
+
+		let lock2 = lock.clone();
+		let result = thread::spawn(move || {
+			let mut w = lock2.write().unwrap();
+			*w += 1;
+			panic!("poisoning the lock");
+		}).join();
+		assert!(result.is_err());
+		assert!(lock.is_poisoned());
+
+		let mut lock = Arc::try_unwrap(lock).unwrap_or_else(|_| panic!("lock still shared"));
+		assert!(lock.get_mut().is_err());
+		match lock.into_inner() {
+			Ok(_) => panic!("expected into_inner to report poisoning"),
+			Err(err) => assert_eq!(err.into_inner(), 1),
+		}
+	}
+}